@@ -0,0 +1,237 @@
+//! LZ4 block compression.
+
+use std::convert::TryInto;
+use std::fmt;
+
+use super::{MAX_DISTANCE, MINMATCH};
+
+const HASH_LOG: u32 = 16;
+const HASH_TABLE_SIZE: usize = 1 << HASH_LOG;
+
+/// An error that occurred while compressing a block.
+#[derive(Debug)]
+pub enum CompressError {
+    /// The output buffer is too small to hold the compressed data.
+    OutputTooSmall { expected_size: usize, actual_size: usize },
+}
+
+impl fmt::Display for CompressError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CompressError::OutputTooSmall { expected_size, actual_size } => write!(
+                f,
+                "output buffer is too small, expected at least {} bytes, got {}",
+                expected_size, actual_size
+            ),
+        }
+    }
+}
+
+impl std::error::Error for CompressError {}
+
+/// Returns the maximum size a block of `uncompressed_size` bytes could compress to.
+///
+/// This is always big enough to hold the compressed output, even for incompressible
+/// input, and is the size callers should allocate before calling [`compress_into`].
+pub fn get_maximum_output_size(uncompressed_size: usize) -> usize {
+    uncompressed_size + (uncompressed_size / 255) + 16
+}
+
+#[inline]
+fn hash(sequence: u32) -> usize {
+    ((sequence.wrapping_mul(2654435761_u32)) >> (32 - HASH_LOG)) as usize
+}
+
+#[inline]
+fn read_u32(data: &[u8], pos: usize) -> u32 {
+    u32::from_le_bytes(data[pos..pos + 4].try_into().unwrap())
+}
+
+/// Maps the hash of a 4-byte sequence to the most recent position it was seen at.
+///
+/// Positions are relative to the start of the input currently being compressed.
+/// When a dictionary is in use, dictionary positions are negative, counting back
+/// from the start of the real input: the last byte of the dictionary is at position
+/// `-1`. This lets match-finding treat `dict` and `input` as one contiguous history
+/// without ever copying the dictionary into the input buffer.
+pub(crate) struct HashTable {
+    table: Vec<i32>,
+}
+
+impl HashTable {
+    pub(crate) fn new() -> Self {
+        Self { table: vec![i32::MIN; HASH_TABLE_SIZE] }
+    }
+
+    #[inline]
+    fn get(&self, seq: u32) -> i32 {
+        self.table[hash(seq)]
+    }
+
+    #[inline]
+    fn put(&mut self, seq: u32, pos: i32) {
+        self.table[hash(seq)] = pos;
+    }
+
+    /// Inserts every 4-byte sequence of `dict` (or its trailing `MAX_DISTANCE` bytes,
+    /// since offsets can't reach any further back) at negative positions, so the
+    /// first matches found while compressing the real input can reference into it.
+    pub(crate) fn insert_dict(&mut self, dict: &[u8]) {
+        let dict = if dict.len() > MAX_DISTANCE { &dict[dict.len() - MAX_DISTANCE..] } else { dict };
+        if dict.len() < MINMATCH {
+            return;
+        }
+        let base = -(dict.len() as i64);
+        for i in 0..=dict.len() - MINMATCH {
+            let seq = read_u32(dict, i);
+            self.put(seq, (base + i as i64) as i32);
+        }
+    }
+}
+
+/// Reads the byte at `pos`, where negative positions index into `dict` (counting back
+/// from the start of `input`) and non-negative positions index into `input`.
+#[inline]
+fn byte_at(dict: &[u8], input: &[u8], pos: i64) -> u8 {
+    if pos < 0 {
+        dict[(dict.len() as i64 + pos) as usize]
+    } else {
+        input[pos as usize]
+    }
+}
+
+/// Returns how many further bytes starting at `a_pos`/`b_pos` compare equal, used to
+/// extend a match past its first 4 confirmed bytes. `b_pos` is always a position
+/// within `input`.
+#[inline]
+fn count_match(dict: &[u8], input: &[u8], mut a_pos: i64, mut b_pos: usize) -> usize {
+    let start = b_pos;
+    while b_pos < input.len() && byte_at(dict, input, a_pos) == input[b_pos] {
+        a_pos += 1;
+        b_pos += 1;
+    }
+    b_pos - start
+}
+
+fn write_integer(output: &mut Vec<u8>, mut len: usize) {
+    while len >= 0xFF {
+        output.push(0xFF);
+        len -= 0xFF;
+    }
+    output.push(len as u8);
+}
+
+/// Compresses `input` into the LZ4 block format using the given hash table, which the
+/// caller may have pre-seeded (with a dictionary, or with the tail of a previous
+/// block for linked mode).
+pub(crate) fn compress_into_with_table(input: &[u8], output: &mut Vec<u8>, dict: &[u8], table: &mut HashTable) {
+    let mut anchor = 0usize;
+    let mut pos = 0usize;
+
+    if input.len() >= MINMATCH {
+        while pos <= input.len() - MINMATCH {
+            let seq = read_u32(input, pos);
+            let candidate = table.get(seq);
+            table.put(seq, pos as i32);
+
+            let in_range =
+                candidate != i32::MIN && (pos as i64 - candidate as i64) as usize <= MAX_DISTANCE;
+            let is_match = in_range
+                && (0..MINMATCH).all(|i| byte_at(dict, input, candidate as i64 + i as i64) == input[pos + i]);
+
+            if !is_match {
+                pos += 1;
+                continue;
+            }
+
+            let offset = (pos as i64 - candidate as i64) as usize;
+            let match_len = MINMATCH + count_match(dict, input, candidate as i64 + MINMATCH as i64, pos + MINMATCH);
+
+            let literal_len = pos - anchor;
+            let token_pos = output.len();
+            output.push(0); // patched once we know the match length's token nibble
+            let lit_nibble = literal_len.min(0xF);
+            if literal_len >= 0xF {
+                write_integer(output, literal_len - 0xF);
+            }
+            output.extend_from_slice(&input[anchor..pos]);
+            output.extend_from_slice(&(offset as u16).to_le_bytes());
+
+            let ext_len = match_len - MINMATCH;
+            let match_nibble = ext_len.min(0xF);
+            if ext_len >= 0xF {
+                write_integer(output, ext_len - 0xF);
+            }
+            output[token_pos] = ((lit_nibble as u8) << 4) | match_nibble as u8;
+
+            pos += match_len;
+            anchor = pos;
+        }
+    }
+
+    // Final literal run; LZ4 blocks always end on literals, never a match.
+    let literal_len = input.len() - anchor;
+    let token_pos = output.len();
+    output.push(0);
+    let lit_nibble = literal_len.min(0xF);
+    if literal_len >= 0xF {
+        write_integer(output, literal_len - 0xF);
+    }
+    output.extend_from_slice(&input[anchor..]);
+    output[token_pos] = (lit_nibble as u8) << 4;
+}
+
+/// Compresses `input` into `output`, which must be at least
+/// [`get_maximum_output_size`]`(input.len())` bytes long, and returns the number of
+/// bytes written.
+pub fn compress_into(input: &[u8], output: &mut [u8]) -> Result<usize, CompressError> {
+    compress_into_with_dict(input, output, &[])
+}
+
+/// Like [`compress_into`], but seeds the match finder with `dict` so that matches can
+/// reference back into it, as if `dict` immediately preceded `input`. Only
+/// the trailing 64KB of `dict` is usable, since that's the largest offset a block can
+/// encode.
+pub fn compress_into_with_dict(input: &[u8], output: &mut [u8], dict: &[u8]) -> Result<usize, CompressError> {
+    let required = get_maximum_output_size(input.len());
+    if output.len() < required {
+        return Err(CompressError::OutputTooSmall { expected_size: required, actual_size: output.len() });
+    }
+    let mut buf = Vec::with_capacity(required);
+    let mut table = HashTable::new();
+    table.insert_dict(dict);
+    compress_into_with_table(input, &mut buf, dict, &mut table);
+    output[..buf.len()].copy_from_slice(&buf);
+    Ok(buf.len())
+}
+
+/// Compresses `input`, returning a freshly allocated buffer with just the compressed
+/// bytes.
+pub fn compress(input: &[u8]) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(get_maximum_output_size(input.len()));
+    let mut table = HashTable::new();
+    compress_into_with_table(input, &mut buf, &[], &mut table);
+    buf
+}
+
+/// Compresses `input`, prepending the uncompressed size as a little-endian `u32` so
+/// that [`decompress_size_prepended`] can size its output buffer without the caller
+/// tracking it separately.
+pub fn compress_prepend_size(input: &[u8]) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(4 + get_maximum_output_size(input.len()));
+    buf.extend_from_slice(&(input.len() as u32).to_le_bytes());
+    let mut table = HashTable::new();
+    compress_into_with_table(input, &mut buf, &[], &mut table);
+    buf
+}
+
+/// Like [`compress_prepend_size`], but seeds the match finder with a dictionary (see
+/// [`compress_into_with_dict`]).
+pub fn compress_prepend_size_with_dict(input: &[u8], dict: &[u8]) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(4 + get_maximum_output_size(input.len()));
+    buf.extend_from_slice(&(input.len() as u32).to_le_bytes());
+    let mut table = HashTable::new();
+    table.insert_dict(dict);
+    compress_into_with_table(input, &mut buf, dict, &mut table);
+    buf
+}
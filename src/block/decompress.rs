@@ -0,0 +1,232 @@
+//! LZ4 block decompression.
+
+use std::convert::TryInto;
+use std::fmt;
+
+/// An error that occurred while decompressing a block.
+#[derive(Debug)]
+pub enum DecompressError {
+    /// The uncompressed size prepended to the input didn't match the size of the
+    /// decompressed output.
+    UncompressedSizeDiffers { expected: usize, actual: usize },
+    /// A literal run reached past the end of the input.
+    LiteralOutOfBounds,
+    /// A copy's offset pointed further back than any available history (the output
+    /// produced so far, plus the dictionary if any).
+    OffsetOutOfBounds,
+    /// An offset of zero is never valid; there is no such thing as a zero-distance
+    /// back-reference.
+    OffsetIsZero,
+    /// The input ended in the middle of a token, a length byte or the 4-byte
+    /// prepended size.
+    ExpectedAnotherByte,
+    /// The decompressed output would have grown past the caller-provided size cap.
+    /// Guards against a crafted block whose copies encode a far larger output than
+    /// its compressed size would suggest (a decompression bomb).
+    OutputTooLarge { max: usize },
+}
+
+impl fmt::Display for DecompressError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecompressError::UncompressedSizeDiffers { expected, actual } => write!(
+                f,
+                "decompressed size {} differs from the prepended size {}",
+                actual, expected
+            ),
+            DecompressError::LiteralOutOfBounds => write!(f, "literal run out of bounds"),
+            DecompressError::OffsetOutOfBounds => write!(f, "copy offset points before the start of history"),
+            DecompressError::OffsetIsZero => write!(f, "copy offset is zero"),
+            DecompressError::ExpectedAnotherByte => write!(f, "input ended unexpectedly"),
+            DecompressError::OutputTooLarge { max } => write!(f, "decompressed output exceeds the {} byte cap", max),
+        }
+    }
+}
+
+impl std::error::Error for DecompressError {}
+
+#[inline]
+fn read_u32(input: &[u8], pos: usize) -> Result<u32, DecompressError> {
+    let bytes: [u8; 4] = input
+        .get(pos..pos + 4)
+        .ok_or(DecompressError::ExpectedAnotherByte)?
+        .try_into()
+        .unwrap();
+    Ok(u32::from_le_bytes(bytes))
+}
+
+/// Decompresses a single LZ4 block from `input` into `output`, treating `dict` as
+/// history that precedes `output`: a copy whose offset reaches before the start of
+/// `output` reads from the tail of `dict` instead. Bounds checks therefore compare
+/// against `dict.len() + output.len()`, the effective length of history available so
+/// far, rather than just `output.len()`.
+///
+/// `max_output_len` caps how large `output` (beyond what it already held on entry)
+/// is allowed to grow before bailing with [`DecompressError::OutputTooLarge`] —
+/// callers decompressing trusted, pre-sized input can pass `usize::MAX` to disable
+/// the check.
+///
+/// Returns the number of bytes read from `input`.
+pub(crate) fn decompress_into_with_history(
+    input: &[u8],
+    output: &mut Vec<u8>,
+    dict: &[u8],
+    max_output_len: usize,
+) -> Result<usize, DecompressError> {
+    let start_len = output.len();
+    let mut in_pos = 0usize;
+
+    loop {
+        let token = *input.get(in_pos).ok_or(DecompressError::ExpectedAnotherByte)?;
+        in_pos += 1;
+
+        let mut literal_len = (token >> 4) as usize;
+        if literal_len == 0xF {
+            loop {
+                let byte = *input.get(in_pos).ok_or(DecompressError::ExpectedAnotherByte)?;
+                in_pos += 1;
+                literal_len += byte as usize;
+                if byte != 0xFF {
+                    break;
+                }
+            }
+        }
+
+        if output.len() - start_len + literal_len > max_output_len {
+            return Err(DecompressError::OutputTooLarge { max: max_output_len });
+        }
+        let literals = input
+            .get(in_pos..in_pos + literal_len)
+            .ok_or(DecompressError::LiteralOutOfBounds)?;
+        output.extend_from_slice(literals);
+        in_pos += literal_len;
+
+        // The last sequence in a block is literals-only, with no trailing offset.
+        if in_pos >= input.len() {
+            break;
+        }
+
+        let offset = u16::from_le_bytes(
+            input
+                .get(in_pos..in_pos + 2)
+                .ok_or(DecompressError::ExpectedAnotherByte)?
+                .try_into()
+                .unwrap(),
+        ) as usize;
+        in_pos += 2;
+        if offset == 0 {
+            return Err(DecompressError::OffsetIsZero);
+        }
+
+        let mut match_len = (token & 0xF) as usize;
+        if match_len == 0xF {
+            loop {
+                let byte = *input.get(in_pos).ok_or(DecompressError::ExpectedAnotherByte)?;
+                in_pos += 1;
+                match_len += byte as usize;
+                if byte != 0xFF {
+                    break;
+                }
+            }
+        }
+        match_len += 4;
+
+        // Checked before the copy loop below, which can't be interrupted part-way
+        // through without leaving `output` at an inconsistent length: `match_len`
+        // is decoded from a handful of continuation bytes and can vastly overstate
+        // the compressed input's size (a decompression bomb), so this has to catch
+        // it before the loop starts copying, not part-way through.
+        if output.len() - start_len + match_len > max_output_len {
+            return Err(DecompressError::OutputTooLarge { max: max_output_len });
+        }
+
+        let history_len = dict.len() + output.len();
+        if offset > history_len {
+            return Err(DecompressError::OffsetOutOfBounds);
+        }
+
+        // Copy byte-by-byte: for `offset < match_len` the copy must observe bytes it
+        // has itself just written (a run-length-encoded repeat).
+        let mut copy_from = output.len() + dict.len() - offset;
+        for _ in 0..match_len {
+            let byte = if copy_from < dict.len() {
+                dict[copy_from]
+            } else {
+                output[copy_from - dict.len()]
+            };
+            output.push(byte);
+            copy_from += 1;
+        }
+    }
+
+    Ok(in_pos)
+}
+
+/// Decompresses `input`, a single LZ4 block, appending the decompressed bytes to
+/// `output` (which is grown as needed — it doesn't need to be pre-sized).
+pub fn decompress_into(input: &[u8], output: &mut Vec<u8>) -> Result<usize, DecompressError> {
+    decompress_into_with_dict(input, output, &[])
+}
+
+/// Like [`decompress_into`], but treats `dict` as history preceding `output`, so that
+/// copies produced with [`compress_into_with_dict`](super::compress_into_with_dict)
+/// can be resolved.
+pub fn decompress_into_with_dict(
+    input: &[u8],
+    output: &mut Vec<u8>,
+    dict: &[u8],
+) -> Result<usize, DecompressError> {
+    decompress_into_with_history(input, output, dict, usize::MAX)
+}
+
+/// Decompresses `input`, a single LZ4 block, into a freshly allocated `Vec` that
+/// starts with `min_uncompressed_size` bytes of capacity and grows if the output is
+/// larger.
+pub fn decompress(input: &[u8], min_uncompressed_size: usize) -> Result<Vec<u8>, DecompressError> {
+    decompress_with_dict(input, min_uncompressed_size, &[])
+}
+
+/// Like [`decompress`], but seeded with a dictionary (see [`decompress_into_with_dict`]).
+pub fn decompress_with_dict(
+    input: &[u8],
+    min_uncompressed_size: usize,
+    dict: &[u8],
+) -> Result<Vec<u8>, DecompressError> {
+    let mut output = Vec::with_capacity(min_uncompressed_size);
+    decompress_into_with_history(input, &mut output, dict, usize::MAX)?;
+    Ok(output)
+}
+
+/// Decompresses `input`, which must have been produced by
+/// [`compress_prepend_size`](super::compress_prepend_size): the first 4 bytes are the
+/// little-endian uncompressed size, followed by the compressed block.
+pub fn decompress_size_prepended(input: &[u8]) -> Result<Vec<u8>, DecompressError> {
+    decompress_size_prepended_with_dict(input, &[])
+}
+
+/// Like [`decompress_size_prepended`], but seeded with a dictionary (see
+/// [`decompress_into_with_dict`]).
+pub fn decompress_size_prepended_with_dict(input: &[u8], dict: &[u8]) -> Result<Vec<u8>, DecompressError> {
+    let uncompressed_size = read_u32(input, 0)? as usize;
+    let mut output = Vec::with_capacity(uncompressed_size);
+    decompress_into_with_history(&input[4..], &mut output, dict, usize::MAX)?;
+    if output.len() != uncompressed_size {
+        return Err(DecompressError::UncompressedSizeDiffers { expected: uncompressed_size, actual: output.len() });
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::compress::compress_prepend_size_with_dict;
+    use super::decompress_size_prepended_with_dict;
+
+    #[test]
+    fn round_trips_with_dictionary() {
+        let dict = b"the quick brown fox jumps over the lazy dog".repeat(100);
+        let input = b"the quick brown fox jumps over the lazy dog again and again".repeat(50);
+        let compressed = compress_prepend_size_with_dict(&input, &dict);
+        let decompressed = decompress_size_prepended_with_dict(&compressed, &dict).unwrap();
+        assert_eq!(decompressed, input);
+    }
+}
@@ -0,0 +1,31 @@
+//! The LZ4 block format, see <https://github.com/lz4/lz4/blob/dev/doc/lz4_Block_format.md>.
+//!
+//! A block has no header of its own; callers that need to know the uncompressed size
+//! up front (e.g. [`compress_prepend_size`]/[`decompress_size_prepended`]) encode it
+//! themselves.
+
+mod compress;
+mod decompress;
+
+pub use compress::{
+    compress, compress_into, compress_into_with_dict, compress_prepend_size,
+    compress_prepend_size_with_dict, get_maximum_output_size, CompressError,
+};
+pub use decompress::{
+    decompress, decompress_into, decompress_into_with_dict, decompress_size_prepended,
+    decompress_size_prepended_with_dict, decompress_with_dict, DecompressError,
+};
+
+// Used by the frame codec, which needs to seed/carry a match-finder table and a
+// decompression history window across block and frame boundaries (dictionaries,
+// linked blocks).
+pub(crate) use compress::{compress_into_with_table, HashTable};
+pub(crate) use decompress::decompress_into_with_history;
+
+/// Minimum length (in bytes) of a match that the compressor is allowed to emit.
+pub(crate) const MINMATCH: usize = 4;
+
+/// The largest offset a single back-reference can encode, and therefore the largest
+/// amount of history (real input and/or a prepended dictionary) a match can reach
+/// into.
+pub(crate) const MAX_DISTANCE: usize = u16::MAX as usize;
@@ -0,0 +1,264 @@
+//! Writing the LZ4 frame format.
+
+use std::hash::Hasher;
+use std::io::{self, Write};
+
+use twox_hash::XxHash32;
+
+use super::header::{BlockInfo, BlockMode, FrameInfo, MAGIC_NUMBER};
+use crate::block::{self, MAX_DISTANCE};
+
+/// Wraps a writer, compressing everything written to it into the LZ4 frame format.
+///
+/// Blocks are buffered up to the configured [`BlockSize`](super::BlockSize) and
+/// flushed as they fill; call [`finish`](FrameEncoder::finish) once all input has
+/// been written to flush the last (possibly partial) block and write the frame's end
+/// mark and content checksum.
+pub struct FrameEncoder<W: Write> {
+    // `Option` so `finish` can move `w` out without a partial move out of a type
+    // that implements `Drop` (E0509); always `Some` until `finish` takes it.
+    w: Option<W>,
+    frame_info: FrameInfo,
+    dict: Vec<u8>,
+    buffer: Vec<u8>,
+    /// In [`BlockMode::Linked`], the trailing up-to-64KB of already-compressed
+    /// output, carried over so the next block's matches can reach back into it.
+    /// Unused (and left empty) in [`BlockMode::Independent`].
+    history: Vec<u8>,
+    content_hasher: XxHash32,
+    header_written: bool,
+    finished: bool,
+}
+
+impl<W: Write> FrameEncoder<W> {
+    /// Creates a new encoder writing frames with the default settings (64KB
+    /// independent blocks, no checksums).
+    pub fn new(w: W) -> Self {
+        Self::with_frame_info(FrameInfo::default(), w)
+    }
+
+    pub(crate) fn with_frame_info(frame_info: FrameInfo, w: W) -> Self {
+        Self {
+            w: Some(w),
+            frame_info,
+            dict: Vec::new(),
+            buffer: Vec::new(),
+            history: Vec::new(),
+            content_hasher: XxHash32::with_seed(0),
+            header_written: false,
+            finished: false,
+        }
+    }
+
+    /// Seeds compression with `dict`, so that early blocks can back-reference into
+    /// it, and records `dict_id` in the frame header so a [`FrameDecoder`](super::FrameDecoder)
+    /// can confirm it's using the same dictionary. Only the trailing
+    /// [`MAX_DICTIONARY_SIZE`](super::MAX_DICTIONARY_SIZE) bytes of `dict` are usable;
+    /// offsets can't reach any further back than that.
+    pub fn with_dictionary(mut self, dict: Vec<u8>, dict_id: Option<u32>) -> Self {
+        self.frame_info.dict_id = dict_id;
+        self.dict = dict;
+        self
+    }
+
+    /// Sets whether blocks may reference data from the previous block
+    /// ([`BlockMode::Linked`], better ratio) or must stand on their own
+    /// ([`BlockMode::Independent`], the default).
+    pub fn block_mode(mut self, mode: BlockMode) -> Self {
+        self.frame_info.block_mode = mode;
+        self
+    }
+
+    fn block_size(&self) -> usize {
+        self.frame_info.block_size.get_size()
+    }
+
+    /// The history a block about to be compressed should be seeded with: the
+    /// dictionary alone in `Independent` mode, or the carried-over window (which
+    /// itself starts out as the dictionary) in `Linked` mode.
+    fn history(&self) -> &[u8] {
+        match self.frame_info.block_mode {
+            BlockMode::Independent => &self.dict,
+            // Before the first block, `self.history` hasn't been seeded yet; fall
+            // back to the dictionary so it's usable for the first block too.
+            BlockMode::Linked if self.history.is_empty() => &self.dict,
+            BlockMode::Linked => &self.history,
+        }
+    }
+
+    /// Updates the carried-over window after compressing `block`, keeping only the
+    /// trailing `MAX_DISTANCE` bytes (the largest offset a match can encode).
+    ///
+    /// Takes `history`/`dict`/`block_mode` as separate arguments, rather than being a
+    /// `&mut self` method, so `flush_block` can call it while still holding a
+    /// borrow of `self.buffer` as the block to extend with.
+    fn extend_history(history: &mut Vec<u8>, dict: &[u8], block_mode: BlockMode, block: &[u8]) {
+        if block_mode != BlockMode::Linked {
+            return;
+        }
+        if history.is_empty() {
+            *history = dict.to_vec();
+        }
+        history.extend_from_slice(block);
+        if history.len() > MAX_DISTANCE {
+            let drop = history.len() - MAX_DISTANCE;
+            history.drain(..drop);
+        }
+    }
+
+    fn write_frame_header(&mut self) -> io::Result<()> {
+        let w = self.w.as_mut().expect("FrameEncoder used after finish");
+        w.write_all(&MAGIC_NUMBER.to_le_bytes())?;
+        w.write_all(&self.frame_info.to_bytes())?;
+        self.header_written = true;
+        Ok(())
+    }
+
+    fn flush_block(&mut self) -> io::Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+
+        let history = self.history().to_vec();
+        let mut compressed = Vec::with_capacity(block::get_maximum_output_size(self.buffer.len()));
+        let mut table = block::HashTable::new();
+        table.insert_dict(&history);
+        block::compress_into_with_table(&self.buffer, &mut compressed, &history, &mut table);
+
+        // Borrowing `self.w` and `self.buffer` as separate fields (rather than
+        // through a `&mut self`-receiver accessor) keeps them disjoint, so this
+        // doesn't need to take or clone `self.buffer` the way a whole-`self`
+        // borrow would.
+        let block_checksums = self.frame_info.block_checksums;
+        let w = self.w.as_mut().expect("FrameEncoder used after finish");
+        if compressed.len() < self.buffer.len() {
+            w.write_all(&BlockInfo::compressed_len_bytes(compressed.len()))?;
+            w.write_all(&compressed)?;
+            if block_checksums {
+                Self::write_block_checksum(w, &compressed)?;
+            }
+        } else {
+            w.write_all(&BlockInfo::uncompressed_len_bytes(self.buffer.len()))?;
+            w.write_all(&self.buffer)?;
+            if block_checksums {
+                Self::write_block_checksum(w, &self.buffer)?;
+            }
+        }
+
+        Self::extend_history(&mut self.history, &self.dict, self.frame_info.block_mode, &self.buffer);
+        self.buffer.clear();
+        Ok(())
+    }
+
+    /// Takes `w` as a separate argument, rather than being a `&mut self` method,
+    /// so callers that already hold a disjoint borrow of `self.w` (to write the
+    /// block data first) can pass it straight through instead of re-borrowing
+    /// `self` as a whole, which would conflict with a live borrow of `self.buffer`
+    /// or `self.history` used to compute `data`.
+    fn write_block_checksum(w: &mut W, data: &[u8]) -> io::Result<()> {
+        let mut hasher = XxHash32::with_seed(0);
+        hasher.write(data);
+        let checksum = (hasher.finish() as u32).to_le_bytes();
+        w.write_all(&checksum)
+    }
+
+    /// Flushes any buffered data, writes the frame's end mark (and content checksum,
+    /// if enabled), and returns the underlying writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        // Set before any fallible write below, not just on success: `finish` takes
+        // `self` by value, so a `?`-propagated I/O error here still runs `Drop`
+        // afterwards, and `Drop` must not redo writes this call already attempted
+        // (which would duplicate/garble whatever made it to `w`).
+        self.finished = true;
+        if !self.header_written {
+            self.write_frame_header()?;
+        }
+        self.flush_block()?;
+        let w = self.w.as_mut().expect("FrameEncoder used after finish");
+        w.write_all(&BlockInfo::END_MARK)?;
+        if self.frame_info.content_checksum {
+            let checksum = self.content_hasher.finish() as u32;
+            w.write_all(&checksum.to_le_bytes())?;
+        }
+        // `w` is `Option` so this can take it by value rather than partially moving
+        // a field out of `self`, which `Drop` on this type would otherwise forbid.
+        Ok(self.w.take().expect("w taken exactly once"))
+    }
+}
+
+impl<W: Write> Write for FrameEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if !self.header_written {
+            self.write_frame_header()?;
+        }
+        if self.frame_info.content_checksum {
+            self.content_hasher.write(buf);
+        }
+
+        let mut written = 0;
+        let block_size = self.block_size();
+        while written < buf.len() {
+            let space = block_size - self.buffer.len();
+            let take = space.min(buf.len() - written);
+            self.buffer.extend_from_slice(&buf[written..written + take]);
+            written += take;
+            if self.buffer.len() == block_size {
+                self.flush_block()?;
+            }
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.w.as_mut().expect("FrameEncoder used after finish").flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::decompress::FrameDecoder;
+    use super::super::header::{BlockMode, BlockSize, FrameInfo};
+    use super::FrameEncoder;
+    use std::io::Read;
+
+    #[test]
+    fn round_trips_linked_blocks() {
+        // Several times past one 64KB block, so at least one block's matches have to
+        // reach back across the boundary into the previous block's history.
+        let input = b"the quick brown fox jumps over the lazy dog".repeat(5000);
+
+        let frame_info = FrameInfo { block_size: BlockSize::Max64KB, block_mode: BlockMode::Linked, ..FrameInfo::default() };
+        let mut encoder = FrameEncoder::with_frame_info(frame_info, Vec::new());
+        std::io::copy(&mut &input[..], &mut encoder).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let mut decoder = FrameDecoder::new(&compressed[..]);
+        let mut output = Vec::new();
+        decoder.read_to_end(&mut output).unwrap();
+        assert_eq!(output, input);
+    }
+}
+
+impl<W: Write> Drop for FrameEncoder<W> {
+    // Best-effort: mirrors `finish`, but errors can't be reported from `drop`. Callers
+    // that care about I/O errors on the final flush should call `finish` explicitly.
+    // `finish` sets `finished` as its very first step, before any fallible write, so
+    // a `FrameEncoder` dropped after a failed `finish` call lands here with
+    // `finished == true` and this is skipped rather than replaying writes `finish`
+    // already attempted.
+    fn drop(&mut self) {
+        if self.finished {
+            return;
+        }
+        if !self.header_written {
+            let _ = self.write_frame_header();
+        }
+        let _ = self.flush_block();
+        let w = self.w.as_mut().expect("FrameEncoder used after finish");
+        let _ = w.write_all(&BlockInfo::END_MARK);
+        if self.frame_info.content_checksum {
+            let checksum = self.content_hasher.finish() as u32;
+            let _ = w.write_all(&checksum.to_le_bytes());
+        }
+    }
+}
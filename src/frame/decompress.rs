@@ -0,0 +1,331 @@
+//! Reading the LZ4 frame format.
+
+use std::hash::Hasher;
+use std::io::{self, Read};
+
+use twox_hash::XxHash32;
+
+use super::header::{BlockInfo, BlockMode, FrameInfo};
+use super::{Error, MAX_SKIPPABLE_FRAME_SIZE};
+use crate::block::{self, MAX_DISTANCE};
+
+/// Callback registered via [`FrameDecoder::on_skippable_frame`], invoked with a
+/// skippable frame's `(magic, payload)`.
+type SkippableFrameCallback = Box<dyn FnMut(u32, &[u8])>;
+
+/// Wraps a [`Read`], decompressing the LZ4 frame read from it, for streaming a
+/// frame off a socket or file without first buffering the whole thing (or even a
+/// whole block) up front.
+///
+/// Reads the frame header lazily, on the first call to [`Read::read`], and then
+/// pulls and decompresses one block at a time as earlier blocks are consumed. At
+/// most one compressed block, its decompressed output and the 64KB history window
+/// (see [`BlockMode::Linked`]) are ever held in memory, regardless of how large the frame's total uncompressed
+/// size is — block and content checksums are validated incrementally as each block
+/// streams through, and [`Read::read`] reports a clean EOF once
+/// [`BlockInfo::EndMark`] is reached.
+pub struct FrameDecoder<R: Read> {
+    r: R,
+    frame_info: Option<FrameInfo>,
+    dict: Vec<u8>,
+    expected_dict_id: Option<u32>,
+    /// In [`BlockMode::Linked`], the trailing up-to-64KB of already-decompressed
+    /// output, carried over so the next block's copies can reach back into it.
+    /// Unused (and left empty) in [`BlockMode::Independent`].
+    history: Vec<u8>,
+    output_buffer: Vec<u8>,
+    output_pos: usize,
+    content_hasher: XxHash32,
+    finished: bool,
+    on_skippable_frame: Option<SkippableFrameCallback>,
+}
+
+impl<R: Read> FrameDecoder<R> {
+    /// Creates a new decoder reading a frame from `r`.
+    pub fn new(r: R) -> Self {
+        Self {
+            r,
+            frame_info: None,
+            dict: Vec::new(),
+            expected_dict_id: None,
+            history: Vec::new(),
+            output_buffer: Vec::new(),
+            output_pos: 0,
+            content_hasher: XxHash32::with_seed(0),
+            finished: false,
+            on_skippable_frame: None,
+        }
+    }
+
+    /// Configures the dictionary blocks were compressed with, so that back-references
+    /// into it can be resolved. If `dict_id` is `Some`, the frame's header `dict_id`
+    /// must match it or decoding fails with [`Error::DictionaryIdMismatch`].
+    pub fn with_dictionary(mut self, dict: Vec<u8>, dict_id: Option<u32>) -> Self {
+        self.dict = dict;
+        self.expected_dict_id = dict_id;
+        self
+    }
+
+    /// Registers a callback invoked with a skippable frame's `(magic, payload)` each
+    /// time one is encountered ahead of a data frame. Without this, a skippable frame
+    /// fails decoding with [`Error::SkippableFrame`]; with it, decoding surfaces the
+    /// frame to `f` and transparently continues on to the next frame.
+    pub fn on_skippable_frame(mut self, f: impl FnMut(u32, &[u8]) + 'static) -> Self {
+        self.on_skippable_frame = Some(Box::new(f));
+        self
+    }
+
+    fn ensure_frame_info(&mut self) -> Result<(), Error> {
+        if self.frame_info.is_some() {
+            return Ok(());
+        }
+        loop {
+            let mut header = vec![0u8; 7];
+            self.r.read_exact(&mut header)?;
+            let required = FrameInfo::required_size(&header)?;
+            if required > header.len() {
+                let mut rest = vec![0u8; required - header.len()];
+                self.r.read_exact(&mut rest)?;
+                header.extend_from_slice(&rest);
+            }
+            let frame_info = match FrameInfo::read(&header) {
+                Ok(frame_info) => frame_info,
+                Err(Error::SkippableFrame { magic, len }) if self.on_skippable_frame.is_some() => {
+                    if len > MAX_SKIPPABLE_FRAME_SIZE {
+                        return Err(Error::SkippableFrameTooLarge { len, max: MAX_SKIPPABLE_FRAME_SIZE });
+                    }
+                    let mut payload = vec![0u8; len];
+                    self.r.read_exact(&mut payload)?;
+                    (self.on_skippable_frame.as_mut().unwrap())(magic, &payload);
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+            if let Some(expected) = self.expected_dict_id {
+                if frame_info.dict_id != Some(expected) {
+                    return Err(Error::DictionaryIdMismatch { expected: Some(expected), actual: frame_info.dict_id });
+                }
+            }
+            self.frame_info = Some(frame_info);
+            return Ok(());
+        }
+    }
+
+    /// The history a block about to be decompressed should be resolved against: the
+    /// dictionary alone in `Independent` mode, or the carried-over window (which
+    /// itself starts out as the dictionary) in `Linked` mode.
+    fn history(&self) -> &[u8] {
+        match self.frame_info.as_ref().unwrap().block_mode {
+            BlockMode::Independent => &self.dict,
+            BlockMode::Linked if self.history.is_empty() => &self.dict,
+            BlockMode::Linked => &self.history,
+        }
+    }
+
+    /// Updates the carried-over window after decompressing `block`, keeping only the
+    /// trailing `MAX_DISTANCE` bytes (the largest offset a copy can encode).
+    fn extend_history(&mut self, block: &[u8]) {
+        if self.frame_info.as_ref().unwrap().block_mode != BlockMode::Linked {
+            return;
+        }
+        if self.history.is_empty() {
+            self.history = self.dict.clone();
+        }
+        self.history.extend_from_slice(block);
+        if self.history.len() > MAX_DISTANCE {
+            let drop = self.history.len() - MAX_DISTANCE;
+            self.history.drain(..drop);
+        }
+    }
+
+    fn check_block_checksum(&self, data: &[u8], expected: u32) -> Result<(), Error> {
+        let mut hasher = XxHash32::with_seed(0);
+        hasher.write(data);
+        if hasher.finish() as u32 != expected {
+            return Err(Error::BlockChecksumError);
+        }
+        Ok(())
+    }
+
+    /// Reads and decompresses the next block, appending it to `output_buffer` and
+    /// updating the carried-over history window. Returns `false` once the frame's
+    /// end mark has been reached.
+    fn pull_block(&mut self) -> Result<bool, Error> {
+        let mut len_buf = [0u8; 4];
+        self.r.read_exact(&mut len_buf)?;
+        let block_info = BlockInfo::read(&len_buf)?;
+
+        let frame_info = self.frame_info.as_ref().unwrap();
+        let content_checksum = frame_info.content_checksum;
+        let block_checksums = frame_info.block_checksums;
+        let max_block_size = frame_info.block_size.get_size();
+
+        // `BlockInfo::read` only bounds `len` to its 28-bit mask (~256MB); validate
+        // it against the frame's actual block size before allocating, since `len`
+        // comes straight off the wire and this type exists to stream untrusted data
+        // without ever holding more than one block's worth of it in memory.
+        if let BlockInfo::Uncompressed(len) | BlockInfo::Compressed(len) = block_info {
+            if len > max_block_size {
+                return Err(Error::BlockTooLarge { len, max: max_block_size });
+            }
+        }
+
+        match block_info {
+            BlockInfo::EndMark => {
+                if content_checksum {
+                    let mut checksum_buf = [0u8; 4];
+                    self.r.read_exact(&mut checksum_buf)?;
+                    let expected = u32::from_le_bytes(checksum_buf);
+                    if self.content_hasher.finish() as u32 != expected {
+                        return Err(Error::ContentChecksumError);
+                    }
+                }
+                self.history.clear();
+                self.finished = true;
+                Ok(false)
+            }
+            BlockInfo::Uncompressed(len) => {
+                let mut data = vec![0u8; len];
+                self.r.read_exact(&mut data)?;
+                if block_checksums {
+                    let mut checksum_buf = [0u8; 4];
+                    self.r.read_exact(&mut checksum_buf)?;
+                    self.check_block_checksum(&data, u32::from_le_bytes(checksum_buf))?;
+                }
+                self.content_hasher.write(&data);
+                self.extend_history(&data);
+                self.output_buffer.extend_from_slice(&data);
+                Ok(true)
+            }
+            BlockInfo::Compressed(len) => {
+                let mut data = vec![0u8; len];
+                self.r.read_exact(&mut data)?;
+                if block_checksums {
+                    let mut checksum_buf = [0u8; 4];
+                    self.r.read_exact(&mut checksum_buf)?;
+                    self.check_block_checksum(&data, u32::from_le_bytes(checksum_buf))?;
+                }
+                let history = self.history().to_vec();
+                let mut block_out = Vec::new();
+                block::decompress_into_with_history(&data, &mut block_out, &history, max_block_size)?;
+                self.content_hasher.write(&block_out);
+                self.extend_history(&block_out);
+                self.output_buffer.extend_from_slice(&block_out);
+                Ok(true)
+            }
+        }
+    }
+}
+
+impl<R: Read> Read for FrameDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.finished && self.output_pos >= self.output_buffer.len() {
+            return Ok(0);
+        }
+
+        self.ensure_frame_info().map_err(to_io_error)?;
+
+        // Drop already-consumed bytes rather than letting `output_buffer` grow for
+        // the lifetime of the decoder; the history window above keeps what later
+        // blocks may still need to reference.
+        if self.output_pos > 0 {
+            self.output_buffer.drain(..self.output_pos);
+            self.output_pos = 0;
+        }
+
+        while self.output_buffer.is_empty() && !self.finished {
+            if !self.pull_block().map_err(to_io_error)? {
+                break;
+            }
+        }
+
+        let available = &self.output_buffer[self.output_pos..];
+        let len = available.len().min(buf.len());
+        buf[..len].copy_from_slice(&available[..len]);
+        self.output_pos += len;
+        Ok(len)
+    }
+}
+
+fn to_io_error(e: Error) -> io::Error {
+    match e {
+        Error::IoError(e) => e,
+        other => io::Error::new(io::ErrorKind::InvalidData, other),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::compress::FrameEncoder;
+    use super::super::header::{BlockSize, FrameInfo};
+    use super::FrameDecoder;
+    use std::io::Read;
+
+    #[test]
+    fn round_trips_a_frame() {
+        let input = b"the quick brown fox jumps over the lazy dog".repeat(1000);
+
+        let mut encoder = FrameEncoder::new(Vec::new());
+        std::io::copy(&mut &input[..], &mut encoder).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let mut decoder = FrameDecoder::new(&compressed[..]);
+        let mut output = Vec::new();
+        decoder.read_to_end(&mut output).unwrap();
+        assert_eq!(output, input);
+    }
+
+    #[test]
+    fn round_trips_multiple_blocks_with_checksums() {
+        // Several times past one 64KB block, with block and content checksums on,
+        // so this exercises `pull_block` being called more than once and the
+        // output buffer draining across block boundaries, not just a single block
+        // fitting entirely in one `read`.
+        let input = b"the quick brown fox jumps over the lazy dog".repeat(5000);
+
+        let frame_info = FrameInfo {
+            block_size: BlockSize::Max64KB,
+            block_checksums: true,
+            content_checksum: true,
+            ..FrameInfo::default()
+        };
+        let mut encoder = FrameEncoder::with_frame_info(frame_info, Vec::new());
+        std::io::copy(&mut &input[..], &mut encoder).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let mut decoder = FrameDecoder::new(&compressed[..]);
+        let mut output = Vec::new();
+        decoder.read_to_end(&mut output).unwrap();
+        assert_eq!(output, input);
+    }
+
+    #[test]
+    fn round_trips_with_a_frame_level_dictionary() {
+        let dict = b"the quick brown fox jumps over the lazy dog".to_vec();
+        let input = b"the quick brown fox jumps over the lazy cat".repeat(50);
+
+        let mut encoder = FrameEncoder::new(Vec::new()).with_dictionary(dict.clone(), Some(42));
+        std::io::copy(&mut &input[..], &mut encoder).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let mut decoder = FrameDecoder::new(&compressed[..]).with_dictionary(dict, Some(42));
+        let mut output = Vec::new();
+        decoder.read_to_end(&mut output).unwrap();
+        assert_eq!(output, input);
+    }
+
+    #[test]
+    fn mismatched_dictionary_id_errors() {
+        let dict = b"the quick brown fox jumps over the lazy dog".to_vec();
+        let input = b"the quick brown fox jumps over the lazy cat".repeat(50);
+
+        let mut encoder = FrameEncoder::new(Vec::new()).with_dictionary(dict.clone(), Some(1));
+        std::io::copy(&mut &input[..], &mut encoder).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let mut decoder = FrameDecoder::new(&compressed[..]).with_dictionary(dict, Some(2));
+        let mut output = Vec::new();
+        let err = decoder.read_to_end(&mut output).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    }
+}
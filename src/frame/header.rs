@@ -21,6 +21,11 @@ mod flags {
     pub const SKIPPABLE_MAGIC: std::ops::RangeInclusive<u32> = 0x184D2A50..=0x184D2A5F;
 }
 
+pub(crate) const MAGIC_NUMBER: u32 = flags::MAGIC_NUMBER;
+pub(crate) const SKIPPABLE_MAGIC: std::ops::RangeInclusive<u32> = flags::SKIPPABLE_MAGIC;
+
+/// The maximum size of a single data block. Larger blocks compress better but use
+/// more memory and add latency (a whole block must be buffered before it's flushed).
 #[derive(Clone, Copy, PartialEq, Debug)]
 pub enum BlockSize {
     Max64KB = 4,
@@ -29,9 +34,15 @@ pub enum BlockSize {
     Max4MB = 7,
 }
 
+/// Whether a block's matches may reach back into the previous block.
 #[derive(Clone, Copy, PartialEq, Debug)]
 pub enum BlockMode {
+    /// Every block stands on its own, decodable without any of the others (the
+    /// default).
     Independent,
+    /// A block's matches may reference up to 64KB of the previous block's data.
+    /// Gives better ratios, at the cost of blocks no longer being independently
+    /// decodable or reorderable.
     Linked,
 }
 
@@ -108,25 +119,28 @@ impl Default for FrameInfo {
 }
 
 impl FrameInfo {
-    pub(crate) fn required_size(mut input: &[u8]) -> Result<usize, Error> {
+    pub(crate) fn required_size(input: &[u8]) -> Result<usize, Error> {
         let mut required = 7;
         if input.len() < 7 {
             return Ok(required);
         }
+        // Read the magic number through a separate slice so `input` still indexes
+        // from position 0 afterwards (`read_exact` on a `&[u8]` advances it).
         let mut magic = [0u8; 4];
-        input.read_exact(&mut magic).map_err(Error::IoError)?;
+        (&input[..4]).read_exact(&mut magic).map_err(Error::IoError)?;
         let magic_num = u32::from_le_bytes(magic);
-        if magic_num != flags::MAGIC_NUMBER {
-            return Err(Error::WrongMagicNumber);
-        }
         if flags::SKIPPABLE_MAGIC.contains(&magic_num) {
             return Ok(8);
         }
+        if magic_num != flags::MAGIC_NUMBER {
+            return Err(Error::WrongMagicNumber);
+        }
 
-        if input[4] & flags::CONTENT_SIZE != 0 {
+        let flag_byte = input[4];
+        if flag_byte & flags::CONTENT_SIZE != 0 {
             required += 8;
         }
-        if input[4] & flags::DICTIONARY_ID != 0 {
+        if flag_byte & flags::DICTIONARY_ID != 0 {
             required += 4
         }
         Ok(required)
@@ -140,14 +154,14 @@ impl FrameInfo {
             input.read_exact(&mut buffer).map_err(Error::IoError)?;
             u32::from_le_bytes(buffer)
         };
-        if magic_num != flags::MAGIC_NUMBER {
-            return Err(Error::WrongMagicNumber);
-        }
         if flags::SKIPPABLE_MAGIC.contains(&magic_num) {
             let mut buffer = [0u8; size_of::<u32>()];
             input.read_exact(&mut buffer).map_err(Error::IoError)?;
             let user_data_len = u32::from_le_bytes(buffer.try_into().unwrap());
-            return Err(Error::SkippableFrame(user_data_len as usize));
+            return Err(Error::SkippableFrame { magic: magic_num, len: user_data_len as usize });
+        }
+        if magic_num != flags::MAGIC_NUMBER {
+            return Err(Error::WrongMagicNumber);
         }
 
         // fixed size section
@@ -170,7 +184,7 @@ impl FrameInfo {
         let content_checksum = flag_byte & flags::CONTENT_CHECKSUM != 0;
         let block_checksums = flag_byte & flags::BLOCK_CHECKSUMS != 0;
 
-        let block_size = match bd_byte & flags::BLOCK_SIZE_MASK >> flags::BLOCK_SIZE_MASK_RSHIFT {
+        let block_size = match (bd_byte & flags::BLOCK_SIZE_MASK) >> flags::BLOCK_SIZE_MASK_RSHIFT {
             i @ 0..=3 => return Err(Error::UnimplementedBlocksize(i)),
             4 => BlockSize::Max64KB,
             5 => BlockSize::Max256KB,
@@ -216,6 +230,50 @@ impl FrameInfo {
             content_checksum,
         })
     }
+
+    /// Serializes the frame descriptor (everything up to and including the header
+    /// checksum), to be written right after the frame magic number.
+    pub(crate) fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(15);
+
+        let mut flag_byte = flags::SUPPORTED_VERSION;
+        if self.block_mode == BlockMode::Independent {
+            flag_byte |= flags::INDEPENDENT_BLOCKS;
+        }
+        if self.block_checksums {
+            flag_byte |= flags::BLOCK_CHECKSUMS;
+        }
+        if self.content_size.is_some() {
+            flag_byte |= flags::CONTENT_SIZE;
+        }
+        if self.content_checksum {
+            flag_byte |= flags::CONTENT_CHECKSUM;
+        }
+        if self.dict_id.is_some() {
+            flag_byte |= flags::DICTIONARY_ID;
+        }
+        out.push(flag_byte);
+
+        let bd_byte = (self.block_size as u8) << flags::BLOCK_SIZE_MASK_RSHIFT;
+        out.push(bd_byte);
+
+        if let Some(content_size) = self.content_size {
+            out.extend_from_slice(&content_size.to_le_bytes());
+        }
+        if let Some(dict_id) = self.dict_id {
+            out.extend_from_slice(&dict_id.to_le_bytes());
+        }
+
+        // `read` folds the magic number into the header checksum (it captures
+        // `original_input` before consuming it), so the hash here has to match.
+        let mut hasher = XxHash32::with_seed(0);
+        hasher.write(&MAGIC_NUMBER.to_le_bytes());
+        hasher.write(&out);
+        let header_hash = (hasher.finish() >> 8) as u8;
+        out.push(header_hash);
+
+        out
+    }
 }
 
 pub(crate) enum BlockInfo {
@@ -225,6 +283,21 @@ pub(crate) enum BlockInfo {
 }
 
 impl BlockInfo {
+    /// Encodes the 4-byte little-endian block size prefix for a block of
+    /// `compressed_len` bytes.
+    pub(crate) fn compressed_len_bytes(compressed_len: usize) -> [u8; 4] {
+        (compressed_len as u32).to_le_bytes()
+    }
+
+    /// Encodes the 4-byte little-endian block size prefix for a block that's stored
+    /// uncompressed (set when compression didn't shrink the block).
+    pub(crate) fn uncompressed_len_bytes(uncompressed_len: usize) -> [u8; 4] {
+        (uncompressed_len as u32 | flags::UNCOMPRESSED_SIZE).to_le_bytes()
+    }
+
+    /// The `EndMark` that terminates a frame's sequence of data blocks.
+    pub(crate) const END_MARK: [u8; 4] = [0, 0, 0, 0];
+
     pub(crate) fn read(mut input: &[u8]) -> Result<Self, Error> {
         let mut size_buffer = [0u8; size_of::<u32>()];
         input.read_exact(&mut size_buffer).map_err(Error::IoError)?;
@@ -240,3 +313,52 @@ impl BlockInfo {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn required_size_matches_a_real_frame_header() {
+        let bytes = FrameInfo::default().to_bytes();
+        let mut header = MAGIC_NUMBER.to_le_bytes().to_vec();
+        header.extend_from_slice(&bytes);
+        let required = FrameInfo::required_size(&header[..7]).unwrap();
+        assert_eq!(required, header.len());
+        FrameInfo::read(&header).unwrap();
+    }
+
+    #[test]
+    fn required_size_detects_a_skippable_frame() {
+        let header = [0x50, 0x2A, 0x4D, 0x18, 0, 0, 0];
+        assert_eq!(FrameInfo::required_size(&header).unwrap(), 8);
+    }
+
+    #[test]
+    fn skippable_frame_surfaces_to_callback_and_decoding_continues() {
+        use super::super::{write_skippable_frame, FrameDecoder, FrameEncoder};
+        use std::io::Read;
+
+        let payload = b"an index or other app-defined side-band data";
+        let input = b"the quick brown fox jumps over the lazy dog".repeat(100);
+
+        let mut compressed = Vec::new();
+        write_skippable_frame(&mut compressed, 0x3, payload).unwrap();
+        let mut encoder = FrameEncoder::new(Vec::new());
+        std::io::copy(&mut &input[..], &mut encoder).unwrap();
+        compressed.extend(encoder.finish().unwrap());
+
+        let seen = std::rc::Rc::new(std::cell::RefCell::new(None));
+        let seen_in_callback = std::rc::Rc::clone(&seen);
+        let mut decoder = FrameDecoder::new(&compressed[..]).on_skippable_frame(move |magic, bytes| {
+            *seen_in_callback.borrow_mut() = Some((magic, bytes.to_vec()));
+        });
+        let mut output = Vec::new();
+        decoder.read_to_end(&mut output).unwrap();
+
+        assert_eq!(output, input);
+        let (magic, bytes) = seen.borrow_mut().take().expect("callback should have fired");
+        assert_eq!(magic, 0x184D2A50 | 0x3);
+        assert_eq!(bytes, payload);
+    }
+}
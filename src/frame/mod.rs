@@ -0,0 +1,132 @@
+//! The LZ4 frame format, see
+//! <https://github.com/lz4/lz4/blob/dev/doc/lz4_Frame_format.md>.
+//!
+//! Unlike the [`block`](crate::block) format, a frame carries its own header
+//! describing how it was encoded (block size, checksums, an optional dictionary ID,
+//! ...), so a [`FrameDecoder`] doesn't need to be told anything about the data ahead
+//! of time beyond, optionally, the dictionary it was compressed with.
+
+use std::fmt;
+use std::io::{self, Write};
+
+mod compress;
+mod decompress;
+mod header;
+
+pub use compress::FrameEncoder;
+pub use decompress::FrameDecoder;
+pub use header::{BlockMode, BlockSize};
+pub(crate) use header::{BlockInfo, FrameInfo};
+
+#[cfg(feature = "parallel")]
+mod par_compress;
+#[cfg(feature = "parallel")]
+pub use par_compress::{par_compress, ParCompress};
+
+/// The largest dictionary a frame can be seeded with. Back-references can encode an
+/// offset of at most 64KB, so any bytes before that are simply unreachable and are
+/// dropped before seeding the match finder / history window.
+pub const MAX_DICTIONARY_SIZE: usize = 64 * 1024;
+
+/// The largest skippable-frame payload a [`FrameDecoder`] with
+/// [`on_skippable_frame`](FrameDecoder::on_skippable_frame) will accept. The format's
+/// length prefix is a full `u32`, but a frame whose declared payload is larger than
+/// this is almost certainly not real user data (an index or an application header is
+/// expected to be small), so [`FrameDecoder`] rejects it up front rather than
+/// allocating a buffer sized off an untrusted length. [`write_skippable_frame`] does
+/// not check payloads against this limit.
+pub const MAX_SKIPPABLE_FRAME_SIZE: usize = 16 * 1024 * 1024;
+
+/// Writes a [skippable frame](https://github.com/lz4/lz4/blob/dev/doc/lz4_Frame_format.md#skippable-frames)
+/// to `w`: a magic number followed by `payload`'s length and bytes, carrying
+/// arbitrary user data that isn't part of the LZ4 bitstream (an index, an
+/// application header, ...) interleaved with real data frames. `nibble` selects
+/// which of the 16 skippable magic numbers (`0x184D2A50..=0x184D2A5F`) to write;
+/// only its low 4 bits are used. A [`FrameDecoder`] configured with
+/// [`on_skippable_frame`](FrameDecoder::on_skippable_frame) surfaces the frame back
+/// to the caller and then continues on to the next frame.
+pub fn write_skippable_frame<W: Write>(w: &mut W, nibble: u8, payload: &[u8]) -> io::Result<()> {
+    let magic = *header::SKIPPABLE_MAGIC.start() | (nibble & 0xF) as u32;
+    w.write_all(&magic.to_le_bytes())?;
+    w.write_all(&(payload.len() as u32).to_le_bytes())?;
+    w.write_all(payload)
+}
+
+/// An error encountered while reading or writing the LZ4 frame format.
+#[derive(Debug)]
+pub enum Error {
+    IoError(std::io::Error),
+    WrongMagicNumber,
+    UnsupportedVersion(u8),
+    UnimplementedBlocksize(u8),
+    HeaderChecksumError,
+    BlockChecksumError,
+    ContentChecksumError,
+    /// A block's length prefix claimed more bytes than the frame's configured
+    /// [`BlockSize`] allows. Rejected before allocating a buffer
+    /// for it, since the prefix is untrusted input (e.g. read off a socket).
+    BlockTooLarge { len: usize, max: usize },
+    /// A [skippable frame](https://github.com/lz4/lz4/blob/dev/doc/lz4_Frame_format.md#skippable-frames)'s
+    /// declared payload length exceeded [`MAX_SKIPPABLE_FRAME_SIZE`]. Rejected
+    /// before allocating a buffer for it, for the same reason as
+    /// [`BlockTooLarge`](Error::BlockTooLarge).
+    SkippableFrameTooLarge { len: usize, max: usize },
+    /// The frame is a [skippable frame](https://github.com/lz4/lz4/blob/dev/doc/lz4_Frame_format.md#skippable-frames),
+    /// carrying `len` bytes of user data rather than compressed content. Returned
+    /// only when the decoder wasn't configured with
+    /// [`on_skippable_frame`](FrameDecoder::on_skippable_frame).
+    SkippableFrame { magic: u32, len: usize },
+    /// The frame's `dict_id` didn't match the dictionary the decoder was configured
+    /// with.
+    DictionaryIdMismatch { expected: Option<u32>, actual: Option<u32> },
+    CompressionError(crate::block::CompressError),
+    DecompressionError(crate::block::DecompressError),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::IoError(e) => write!(f, "io error: {}", e),
+            Error::WrongMagicNumber => write!(f, "wrong magic number"),
+            Error::UnsupportedVersion(v) => write!(f, "unsupported frame version: {:#04b}", v),
+            Error::UnimplementedBlocksize(v) => write!(f, "unimplemented block size: {}", v),
+            Error::HeaderChecksumError => write!(f, "header checksum mismatch"),
+            Error::BlockChecksumError => write!(f, "block checksum mismatch"),
+            Error::ContentChecksumError => write!(f, "content checksum mismatch"),
+            Error::BlockTooLarge { len, max } => {
+                write!(f, "block length {} exceeds the frame's max block size of {}", len, max)
+            }
+            Error::SkippableFrameTooLarge { len, max } => {
+                write!(f, "skippable frame payload length {} exceeds the {} byte cap", len, max)
+            }
+            Error::SkippableFrame { magic, len } => {
+                write!(f, "skippable frame {:#010x} with {} bytes of user data", magic, len)
+            }
+            Error::DictionaryIdMismatch { expected, actual } => {
+                write!(f, "dictionary id mismatch: expected {:?}, got {:?}", expected, actual)
+            }
+            Error::CompressionError(e) => write!(f, "{}", e),
+            Error::DecompressionError(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::IoError(e)
+    }
+}
+
+impl From<crate::block::CompressError> for Error {
+    fn from(e: crate::block::CompressError) -> Self {
+        Error::CompressionError(e)
+    }
+}
+
+impl From<crate::block::DecompressError> for Error {
+    fn from(e: crate::block::DecompressError) -> Self {
+        Error::DecompressionError(e)
+    }
+}
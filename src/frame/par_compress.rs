@@ -0,0 +1,179 @@
+//! Parallel frame compression using a [rayon](https://docs.rs/rayon) thread pool.
+//! Requires the `parallel` feature.
+//!
+//! Only [`BlockMode::Independent`] frames can be compressed this way: since no block
+//! depends on any other, every block can be compressed on its own thread. The output
+//! is byte-identical to what [`FrameEncoder`](super::FrameEncoder) would produce for
+//! the same input and settings (block checksums are computed per worker, and the
+//! content checksum, if enabled, over the original input in order), just produced
+//! faster.
+
+use std::hash::Hasher;
+use std::io::{self, Write};
+
+use rayon::prelude::*;
+use twox_hash::XxHash32;
+
+use super::header::{BlockInfo, BlockMode, BlockSize, FrameInfo, MAGIC_NUMBER};
+use crate::block;
+
+fn xxhash32(data: &[u8]) -> [u8; 4] {
+    let mut hasher = XxHash32::with_seed(0);
+    hasher.write(data);
+    (hasher.finish() as u32).to_le_bytes()
+}
+
+/// Compresses one block's worth of `chunk`, returning the bytes to write for it (the
+/// `BlockInfo` length prefix, the block payload, and an optional checksum) — the
+/// unit of work handed to each thread.
+fn compress_chunk(chunk: &[u8], dict: &[u8], block_checksums: bool) -> Vec<u8> {
+    let mut compressed = Vec::with_capacity(block::get_maximum_output_size(chunk.len()));
+    let mut table = block::HashTable::new();
+    table.insert_dict(dict);
+    block::compress_into_with_table(chunk, &mut compressed, dict, &mut table);
+
+    let mut out = Vec::with_capacity(4 + chunk.len().max(compressed.len()) + 4);
+    if compressed.len() < chunk.len() {
+        out.extend_from_slice(&BlockInfo::compressed_len_bytes(compressed.len()));
+        out.extend_from_slice(&compressed);
+        if block_checksums {
+            out.extend_from_slice(&xxhash32(&compressed));
+        }
+    } else {
+        out.extend_from_slice(&BlockInfo::uncompressed_len_bytes(chunk.len()));
+        out.extend_from_slice(chunk);
+        if block_checksums {
+            out.extend_from_slice(&xxhash32(chunk));
+        }
+    }
+    out
+}
+
+/// Builder for [`compress`](ParCompress::compress), letting callers override the
+/// block size (which doubles as the per-thread chunk size), dictionary, and thread
+/// pool used for parallel frame compression.
+pub struct ParCompress {
+    frame_info: FrameInfo,
+    dict: Vec<u8>,
+    num_threads: Option<usize>,
+}
+
+impl Default for ParCompress {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ParCompress {
+    /// Creates a new builder with the default settings (64KB blocks, no checksums,
+    /// one thread per available core).
+    pub fn new() -> Self {
+        Self { frame_info: FrameInfo::default(), dict: Vec::new(), num_threads: None }
+    }
+
+    /// Sets the block size, which also controls how big a chunk of input each
+    /// worker thread compresses at a time.
+    pub fn block_size(mut self, block_size: BlockSize) -> Self {
+        self.frame_info.block_size = block_size;
+        self
+    }
+
+    /// Enables a checksum of each compressed block, computed on the worker thread
+    /// that produced it.
+    pub fn block_checksums(mut self, block_checksums: bool) -> Self {
+        self.frame_info.block_checksums = block_checksums;
+        self
+    }
+
+    /// Enables a checksum of the whole uncompressed input, computed in input order
+    /// after all blocks have been compressed.
+    pub fn content_checksum(mut self, content_checksum: bool) -> Self {
+        self.frame_info.content_checksum = content_checksum;
+        self
+    }
+
+    /// Seeds every block's compression with `dict` (see
+    /// [`FrameEncoder::with_dictionary`](super::FrameEncoder::with_dictionary)).
+    pub fn with_dictionary(mut self, dict: Vec<u8>, dict_id: Option<u32>) -> Self {
+        self.frame_info.dict_id = dict_id;
+        self.dict = dict;
+        self
+    }
+
+    /// Caps the number of threads used to compress blocks concurrently. Defaults to
+    /// rayon's global thread pool (one thread per core).
+    pub fn num_threads(mut self, num_threads: usize) -> Self {
+        self.num_threads = Some(num_threads);
+        self
+    }
+
+    /// Compresses `input` into the LZ4 frame format, writing it to `w`. Always
+    /// produces [`BlockMode::Independent`] blocks, since that's what makes
+    /// compressing them concurrently possible.
+    pub fn compress<W: Write>(self, input: &[u8], mut w: W) -> io::Result<W> {
+        let mut frame_info = self.frame_info;
+        frame_info.block_mode = BlockMode::Independent;
+
+        w.write_all(&MAGIC_NUMBER.to_le_bytes())?;
+        w.write_all(&frame_info.to_bytes())?;
+
+        let block_size = frame_info.block_size.get_size();
+        let dict = &self.dict;
+        let block_checksums = frame_info.block_checksums;
+        let compress_all = || -> Vec<Vec<u8>> {
+            input
+                .par_chunks(block_size.max(1))
+                .map(|chunk| compress_chunk(chunk, dict, block_checksums))
+                .collect()
+        };
+        let blocks = match self.num_threads {
+            Some(num_threads) => {
+                let pool = rayon::ThreadPoolBuilder::new()
+                    .num_threads(num_threads)
+                    .build()
+                    .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+                pool.install(compress_all)
+            }
+            None => compress_all(),
+        };
+
+        for block in &blocks {
+            w.write_all(block)?;
+        }
+        w.write_all(&BlockInfo::END_MARK)?;
+
+        if frame_info.content_checksum {
+            w.write_all(&xxhash32(input))?;
+        }
+
+        Ok(w)
+    }
+}
+
+/// Compresses `input` into the LZ4 frame format using a rayon thread pool to
+/// compress blocks concurrently, writing it to `w`. Equivalent to
+/// `ParCompress::new().compress(input, w)`; use [`ParCompress`] to configure block
+/// size, checksums, a dictionary, or the thread pool.
+pub fn par_compress<W: Write>(input: &[u8], w: W) -> io::Result<W> {
+    ParCompress::new().compress(input, w)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::compress::FrameEncoder;
+    use super::par_compress;
+    use std::io::Write;
+
+    #[test]
+    fn matches_serial_output_byte_for_byte() {
+        let input = b"the quick brown fox jumps over the lazy dog".repeat(5000);
+
+        let parallel = par_compress(&input, Vec::new()).unwrap();
+
+        let mut encoder = FrameEncoder::new(Vec::new());
+        encoder.write_all(&input).unwrap();
+        let serial = encoder.finish().unwrap();
+
+        assert_eq!(parallel, serial);
+    }
+}
@@ -19,6 +19,7 @@ assert_eq!(input, uncompressed);
 - `safe-decode` uses only safe rust for encode. _enabled by default_
 - `checked-decode` will add additional checks if `safe-decode` is not enabled, to avoid out of bounds access. This should be enabled for untrusted input.
 - `frame` support for LZ4 frame format. _implies `std`, enabled by default_
+- `parallel` compresses frames across multiple threads via `rayon`, see [`frame::par_compress`]. _implies `frame`_
 - `std` enables dependency on the standard library. _enabled by default_
 
 For maximum performance use `no-default-features`.